@@ -1,3 +1,5 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
 use pathfinding::prelude::astar;
 use bevy_math::Vec2;
@@ -8,13 +10,34 @@ const MAX_TURN_ANGLE_DEG: i32 = 5;
 const LEG_DISTANCE: f32 = 20.; // meters
 const SEED: f32 = 0.;
 const NODE_HEIGHT_MULTIPLIER: i32 = 1000; // since the PathNode struct can only take integers, we multiply the height by this and round it.
+const WAYPOINT_RADIUS: f32 = LEG_DISTANCE; // a node within this distance of a target counts as having reached it
+const MAX_ORDERED_WAYPOINTS: usize = 9; // permutation count (n!) gets unreasonable past this
+const DISTANCE_COST_SCALE: f32 = 1.; // cost units per meter of true 3D leg length
+const RAYCAST_STEP: f32 = 5.; // meters between terrain samples along a visibility ray
 
 const NOISE_SCALE: f32 = 100.;
-const NOISE_AMPLITUDE: f32 = 10.;
 
 const WIDTH: u32 = 512;
 const HEIGHT: u32 = 512;
 
+// fbm/continent/ridge defaults used by HeightField::default
+const OCTAVES: u32 = 5;
+const LACUNARITY: f32 = 2.0;
+const PERSISTENCE: f32 = 0.5;
+const CONTINENT_NOISE_SCALE: f32 = 800.;
+const CONTINENT_FACTOR: f32 = 0.1;
+const MOUNTAIN_NOISE_SCALE: f32 = 250.;
+const MOUNTAIN_RANGE_MIX_FACTOR: f32 = 0.075;
+const MIN_ALTITUDE: f32 = -10.;
+const MAX_ALTITUDE: f32 = 50.;
+
+// World units per cache cell. 1.0 rounds lookups to the nearest integer
+// position; lookups at fractional positions (successor candidates, raycast
+// steps) share a cell with nearby points, and a hit returns the height first
+// sampled in that cell. Raising it trades sample precision for a higher cache
+// hit rate.
+const HEIGHT_CACHE_QUANTIZATION: f32 = 1.;
+
 fn get_absolute_slope(dist: f32, val1: f32, val2: f32) -> f32 {
     ((val2 - val1) / dist).abs()
 }
@@ -23,6 +46,144 @@ fn angle_deg_between_vec2(first: Vec2, second: Vec2) -> i32 {
     (second.dot(first) / (second.length() * first.length())).acos().to_degrees() as i32
 }
 
+// Wraps `value` into [0, length) so heightfield sampling repeats with period
+// `length`. The noise layers themselves are not periodic, so heights are not
+// continuous across the wrap boundary.
+fn repeat(value: f32, length: f32) -> f32 {
+    value - (value / length).floor() * length
+}
+
+/// Layered terrain: fractal Brownian motion for detail, a low-frequency
+/// continent mask that pushes ocean basins toward `min_altitude`, and a
+/// ridged noise component mixed in to carve mountain spines.
+struct HeightField {
+    octaves: u32,
+    lacunarity: f32,
+    persistence: f32,
+    continent_factor: f32,
+    mountain_range_mix_factor: f32,
+    min_altitude: f32,
+    max_altitude: f32,
+    seed: f32,
+}
+
+impl Default for HeightField {
+    fn default() -> Self {
+        HeightField {
+            octaves: OCTAVES,
+            lacunarity: LACUNARITY,
+            persistence: PERSISTENCE,
+            continent_factor: CONTINENT_FACTOR,
+            mountain_range_mix_factor: MOUNTAIN_RANGE_MIX_FACTOR,
+            min_altitude: MIN_ALTITUDE,
+            max_altitude: MAX_ALTITUDE,
+            seed: SEED,
+        }
+    }
+}
+
+impl HeightField {
+    fn sample(&self, x: f32, y: f32) -> f32 {
+        let pos = Vec2::new(repeat(x, WIDTH as f32), repeat(y, HEIGHT as f32));
+
+        let mut amplitude = 1.;
+        let mut frequency = 1.;
+        let mut amplitude_sum = 0.;
+        let mut fbm = 0.;
+        for _ in 0..self.octaves {
+            fbm += amplitude * simplex_noise_2d_seeded(pos * frequency / NOISE_SCALE, self.seed);
+            amplitude_sum += amplitude;
+            amplitude *= self.persistence;
+            frequency *= self.lacunarity;
+        }
+        fbm /= amplitude_sum;
+
+        // below-threshold continent noise is pulled down toward the minimum altitude
+        let continent = simplex_noise_2d_seeded(pos / CONTINENT_NOISE_SCALE, self.seed + 1.);
+        let mut value = fbm;
+        if continent < self.continent_factor {
+            let sink = (self.continent_factor - continent) / (self.continent_factor + 1.);
+            value = value * (1. - sink) - sink;
+        }
+
+        // ridged noise spine, mixed in with a small weight to avoid overwhelming the base terrain
+        let ridge = simplex_noise_2d_seeded(pos / MOUNTAIN_NOISE_SCALE, self.seed + 2.);
+        let ridged = 1. - ridge.abs();
+        value = value * (1. - self.mountain_range_mix_factor) + ridged * self.mountain_range_mix_factor;
+
+        self.min_altitude + (value * 0.5 + 0.5).clamp(0., 1.) * (self.max_altitude - self.min_altitude)
+    }
+}
+
+/// Wraps a `HeightField`, memoizing samples by quantized integer position so
+/// repeated lookups at the same world position (common during A* search,
+/// since nodes get revisited) skip the noise evaluation. Tracks hit/miss
+/// counts so callers can verify the cache is actually paying off.
+struct HeightCache<'a> {
+    heightfield: &'a HeightField,
+    cache: HashMap<(i32, i32), f32>,
+    hits: u32,
+    misses: u32,
+}
+
+impl<'a> HeightCache<'a> {
+    fn new(heightfield: &'a HeightField) -> Self {
+        HeightCache { heightfield, cache: HashMap::new(), hits: 0, misses: 0 }
+    }
+
+    fn sample(&mut self, x: f32, y: f32) -> f32 {
+        let key = (
+            (x / HEIGHT_CACHE_QUANTIZATION).round() as i32,
+            (y / HEIGHT_CACHE_QUANTIZATION).round() as i32,
+        );
+        if let Some(&cached) = self.cache.get(&key) {
+            self.hits += 1;
+            return cached;
+        }
+        self.misses += 1;
+        let value = self.heightfield.sample(x, y);
+        self.cache.insert(key, value);
+        value
+    }
+
+    fn hit_rate(&self) -> f32 {
+        let total = self.hits + self.misses;
+        if total == 0 { 0. } else { self.hits as f32 / total as f32 }
+    }
+}
+
+/// Tunes how the search trusts its heuristic and how it costs/prunes edges.
+///
+/// `greedy_factor == 1.0` keeps the heuristic an admissible lower bound, so
+/// `astar` still returns an optimal path. Values above `1.0` let the
+/// heuristic overestimate (weighted / greedy A*), which expands far fewer
+/// nodes on large maps at the cost of optimality.
+///
+/// `max_grade` is the steepest `|slope|` a leg may have; steeper candidates
+/// are dropped from `successors` entirely, forcing the search to find
+/// switchbacks instead of climbing straight up. `grade_penalty_weight` scales
+/// how much a leg's slope adds to its cost, independent of the distance term.
+#[derive(Clone, Copy, Debug)]
+struct SearchConfig {
+    greedy_factor: f32,
+    max_grade: f32,
+    grade_penalty_weight: f32,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig { greedy_factor: 1., max_grade: 0.3, grade_penalty_weight: 1000. }
+    }
+}
+
+/// Bundles the terrain and search tuning a route plan needs, so it doesn't
+/// have to be threaded through every helper individually. The height cache is
+/// shared (and mutated) across every leg of a route, hence the `RefCell`.
+struct RouteContext<'a> {
+    height_cache: &'a RefCell<HeightCache<'a>>,
+    config: &'a SearchConfig,
+}
+
 #[derive(Clone, Debug, Hash, Eq, PartialEq)]
 struct PathNode {
     position: (i32, i32),
@@ -35,7 +196,7 @@ impl PathNode {
         Vec2::new(self.position.0 as f32, self.position.1 as f32)
     }
 
-    fn successors(&self) -> Vec<(Self, u32)> {
+    fn successors(&self, height_cache: &RefCell<HeightCache>, config: &SearchConfig) -> Vec<(Self, u32)> {
         let mut result = Vec::new();
         for angle_deg in ((self.current_world_angle_deg - MAX_TURN_ANGLE_DEG)..(self.current_world_angle_deg + MAX_TURN_ANGLE_DEG)).step_by(1) {
             let angle_rad = f32::to_radians(angle_deg as f32);
@@ -45,8 +206,13 @@ impl PathNode {
             let world_pos_f32 = self.get_world_vec2() + direction_vec2;
             let world_pos_int = (x as i32 + self.position.0, y as i32 + self.position.1);
 
-            let height_here = NOISE_AMPLITUDE * simplex_noise_2d_seeded(world_pos_f32 / NOISE_SCALE, SEED);
-            let slope = get_absolute_slope(LEG_DISTANCE, self.height as f32 / NODE_HEIGHT_MULTIPLIER as f32, height_here);
+            let height_here = height_cache.borrow_mut().sample(world_pos_f32.x, world_pos_f32.y);
+            let prev_height = self.height as f32 / NODE_HEIGHT_MULTIPLIER as f32;
+            let slope = get_absolute_slope(LEG_DISTANCE, prev_height, height_here);
+            if slope > config.max_grade {
+                // too steep to traverse directly; the search has to find a switchback instead
+                continue;
+            }
 
             // create new pathnode instance, determine cost, and add to the result vector
             let node = PathNode {
@@ -54,52 +220,332 @@ impl PathNode {
                 height: (height_here * NODE_HEIGHT_MULTIPLIER as f32) as i32,
                 current_world_angle_deg: angle_deg_between_vec2(direction_vec2, Vec2::new(1.0, 0.0)),
             };
-            let cost = (slope * 1000.) as u32;
+            let dz = height_here - prev_height;
+            let leg_length_3d = (LEG_DISTANCE * LEG_DISTANCE + dz * dz).sqrt();
+            let cost = (leg_length_3d * DISTANCE_COST_SCALE + slope * config.grade_penalty_weight) as u32;
             result.push((node, cost));
         }
 
         result
     }
 
-    fn heuristic(&self) -> u32 {
-        let dist = WIDTH as i32 - self.position.0;
-        if dist < 0 {
-            0
+    // Lower bound in the same units as edge costs: remaining legs to `target`
+    // times the cheapest per-leg cost the current model can produce (a flat,
+    // zero-slope leg only pays the distance term), then scaled by `greedy_factor`.
+    // `min_leg_cost` must track the true floor of `successors`'s cost formula
+    // (currently `DISTANCE_COST_SCALE`'d leg length) — if that floor were ever
+    // 0 again, the heuristic would collapse to 0 and `greedy_factor` would do
+    // nothing, since scaling 0 by any factor is still 0.
+    fn heuristic(&self, target: (i32, i32), config: &SearchConfig) -> u32 {
+        let dist = (self.get_world_vec2().distance(Vec2::new(target.0 as f32, target.1 as f32)) - WAYPOINT_RADIUS).max(0.);
+        let remaining_legs = dist / LEG_DISTANCE;
+        let min_leg_cost = LEG_DISTANCE * DISTANCE_COST_SCALE;
+        ((remaining_legs * min_leg_cost) * config.greedy_factor) as u32
+    }
+
+    fn reached(&self, target: (i32, i32)) -> bool {
+        self.get_world_vec2().distance(Vec2::new(target.0 as f32, target.1 as f32)) <= WAYPOINT_RADIUS
+    }
+}
+
+const HEX_SIZE: f32 = LEG_DISTANCE; // world-space edge length of a hex, sharing the continuous mode's leg scale
+const HEX_DIRECTIONS: [(i32, i32); 6] = [(1, 0), (-1, 0), (0, 1), (0, -1), (1, -1), (-1, 1)];
+
+/// Tile-based alternative to `PathNode`'s continuous angle-sweep expansion,
+/// for games that want a fixed hex grid instead of vehicle-style turning.
+/// Uses axial coordinates `(q, r)`.
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+struct HexNode {
+    q: i32,
+    r: i32,
+}
+
+impl HexNode {
+    fn to_world(&self) -> Vec2 {
+        let sqrt3 = 3f32.sqrt();
+        Vec2::new(
+            HEX_SIZE * (sqrt3 * self.q as f32 + sqrt3 / 2. * self.r as f32),
+            HEX_SIZE * (1.5 * self.r as f32),
+        )
+    }
+
+    // The six neighboring hexes, each costed from the terrain-derived slope
+    // between this hex and the neighbor; hexes too steep to cross (by
+    // `config.max_grade`) are omitted rather than costed.
+    fn successors(&self, height_cache: &RefCell<HeightCache>, config: &SearchConfig) -> Vec<(Self, u32)> {
+        let here_pos = self.to_world();
+        let here_height = height_cache.borrow_mut().sample(here_pos.x, here_pos.y);
+
+        let mut result = Vec::new();
+        for (dq, dr) in HEX_DIRECTIONS {
+            let neighbor = HexNode { q: self.q + dq, r: self.r + dr };
+            let neighbor_pos = neighbor.to_world();
+            let neighbor_height = height_cache.borrow_mut().sample(neighbor_pos.x, neighbor_pos.y);
+            // adjacent hex centers are HEX_SIZE * sqrt(3) apart
+            let slope = get_absolute_slope(HEX_SIZE * 3f32.sqrt(), here_height, neighbor_height);
+            if slope > config.max_grade {
+                continue;
+            }
+            let cost = (HEX_SIZE * DISTANCE_COST_SCALE + slope * config.grade_penalty_weight) as u32;
+            result.push((neighbor, cost));
+        }
+
+        result
+    }
+
+    // Admissible lower bound via cube-coordinate hex distance, scaled by the
+    // cheapest possible per-hex cost (flat ground) so it stays in cost units.
+    fn heuristic(&self, target: &HexNode, config: &SearchConfig) -> u32 {
+        let dq = (target.q - self.q) as f32;
+        let dr = (target.r - self.r) as f32;
+        let hex_distance = (dq.abs() + (dq + dr).abs() + dr.abs()) / 2.;
+        let min_hex_cost = HEX_SIZE * DISTANCE_COST_SCALE;
+        ((hex_distance * min_hex_cost) * config.greedy_factor) as u32
+    }
+
+    fn reached(&self, target: &HexNode) -> bool {
+        self == target
+    }
+}
+
+type Leg = (Vec<PathNode>, u32);
+
+fn find_leg(from: &PathNode, target: (i32, i32), ctx: &RouteContext) -> Option<Leg> {
+    astar(
+        from,
+        |node| node.successors(ctx.height_cache, ctx.config),
+        |node| node.heuristic(target, ctx.config),
+        |node| node.reached(target),
+    )
+}
+
+// A nominal node sitting at `position`, used as the starting state for a leg
+// whose real arrival state (turn angle) isn't known yet, e.g. when comparing
+// waypoint orderings before a path has actually been walked.
+fn waypoint_node(position: (i32, i32), height_cache: &RefCell<HeightCache>) -> PathNode {
+    let height = height_cache.borrow_mut().sample(position.0 as f32, position.1 as f32);
+    PathNode {
+        position,
+        height: (height * NODE_HEIGHT_MULTIPLIER as f32) as i32,
+        current_world_angle_deg: 0,
+    }
+}
+
+/// Plans a path visiting every waypoint in order, by running A* between
+/// consecutive targets and concatenating the resulting node lists (dropping
+/// the duplicate junction node between legs).
+fn route_through(start: &PathNode, waypoints: &[(i32, i32)], ctx: &RouteContext) -> Option<Vec<PathNode>> {
+    let mut path = vec![start.clone()];
+    for &target in waypoints {
+        let (leg, _cost) = find_leg(path.last().unwrap(), target, ctx)?;
+        path.extend(leg.into_iter().skip(1));
+    }
+    Some(path)
+}
+
+// Cost of the leg from point `from_idx` to point `to_idx`, where index 0 is
+// `start` and index `i` (i >= 1) is `waypoints[i - 1]`. Caches each ordered
+// pair in `memo` so the permutation search below never re-solves a leg.
+fn leg_cost(
+    memo: &mut HashMap<(usize, usize), Option<Leg>>,
+    start: &PathNode,
+    waypoints: &[(i32, i32)],
+    ctx: &RouteContext,
+    from_idx: usize,
+    to_idx: usize,
+) -> Option<u32> {
+    if let Some(entry) = memo.get(&(from_idx, to_idx)) {
+        return entry.as_ref().map(|(_, cost)| *cost);
+    }
+    let from = if from_idx == 0 { start.clone() } else { waypoint_node(waypoints[from_idx - 1], ctx.height_cache) };
+    let leg = find_leg(&from, waypoints[to_idx - 1], ctx);
+    let cost = leg.as_ref().map(|(_, cost)| *cost);
+    memo.insert((from_idx, to_idx), leg);
+    cost
+}
+
+// Heap's algorithm: calls `visit` with every permutation of `items[0..k]`,
+// permuting in place rather than allocating one vector per permutation.
+fn heaps_permutations(items: &mut [usize], k: usize, visit: &mut impl FnMut(&[usize])) {
+    if k <= 1 {
+        visit(items);
+        return;
+    }
+    for i in 0..k {
+        heaps_permutations(items, k - 1, visit);
+        if k % 2 == 0 {
+            items.swap(i, k - 1);
         } else {
-            (WIDTH as i32 - self.position.0) as u32
+            items.swap(0, k - 1);
         }
     }
+}
 
-    fn success(&self) -> bool {
-        self.position.0 >= WIDTH as i32
+/// Like `route_through`, but first finds the waypoint visiting order that
+/// minimizes total path cost. Only feasible for small waypoint counts
+/// (`MAX_ORDERED_WAYPOINTS`): every ordering is enumerated and scored by
+/// summing memoized pairwise A* leg costs, and the cheapest is walked.
+fn route_through_optimized_order(start: &PathNode, waypoints: &[(i32, i32)], ctx: &RouteContext) -> Option<Vec<PathNode>> {
+    assert!(
+        waypoints.len() <= MAX_ORDERED_WAYPOINTS,
+        "optimal waypoint ordering only supports up to {} waypoints", MAX_ORDERED_WAYPOINTS
+    );
+    if waypoints.is_empty() {
+        return Some(vec![start.clone()]);
     }
+
+    let mut memo: HashMap<(usize, usize), Option<Leg>> = HashMap::new();
+    let mut order: Vec<usize> = (1..=waypoints.len()).collect();
+    let mut best_order = order.clone();
+    let mut best_cost = u32::MAX;
+
+    heaps_permutations(&mut order, waypoints.len(), &mut |candidate| {
+        let mut total = 0u32;
+        let mut prev = 0usize;
+        for &idx in candidate {
+            match leg_cost(&mut memo, start, waypoints, ctx, prev, idx) {
+                Some(cost) => total = total.saturating_add(cost),
+                None => return,
+            }
+            prev = idx;
+        }
+        if total < best_cost {
+            best_cost = total;
+            best_order = candidate.to_vec();
+        }
+    });
+
+    let ordered_waypoints: Vec<(i32, i32)> = best_order.iter().map(|&idx| waypoints[idx - 1]).collect();
+    route_through(start, &ordered_waypoints, ctx)
+}
+
+// Walks the straight segment from `from` to `to` in `step`-sized increments,
+// sampling `height_cache` at each point. Returns the first point along the
+// way where the slope between consecutive samples exceeds `max_grade` (i.e.
+// where a straight line would have to cut through or climb terrain too
+// steeply), or `None` if the whole segment is clear.
+fn raycast(from: (i32, i32), to: (i32, i32), height_cache: &RefCell<HeightCache>, max_grade: f32, step: f32) -> Option<(f32, f32)> {
+    let from_v = Vec2::new(from.0 as f32, from.1 as f32);
+    let to_v = Vec2::new(to.0 as f32, to.1 as f32);
+    let dist = from_v.distance(to_v);
+    if dist <= 0. {
+        return None;
+    }
+    let dir = (to_v - from_v) / dist;
+    let steps = (dist / step).ceil() as u32;
+
+    let mut prev_travelled = 0.;
+    let mut prev_height = height_cache.borrow_mut().sample(from_v.x, from_v.y);
+    for i in 1..=steps {
+        let travelled = (i as f32 * step).min(dist);
+        let pos = from_v + dir * travelled;
+        let height = height_cache.borrow_mut().sample(pos.x, pos.y);
+        let slope = get_absolute_slope(travelled - prev_travelled, prev_height, height);
+        if slope > max_grade {
+            return Some((pos.x, pos.y));
+        }
+        prev_travelled = travelled;
+        prev_height = height;
+    }
+
+    None
+}
+
+/// String-pulling pass: collapses a dense A* polyline into a much shorter one
+/// by, starting from node `i`, advancing `j` as long as `raycast` between
+/// `nodes[i]` and `nodes[j]` reports clear, then keeping only the endpoints.
+/// Respects the same `max_grade` the search was constrained by.
+fn smooth_path(nodes: &[PathNode], height_cache: &RefCell<HeightCache>, max_grade: f32, step: f32) -> Vec<PathNode> {
+    if nodes.is_empty() {
+        return Vec::new();
+    }
+
+    let mut smoothed = vec![nodes[0].clone()];
+    let mut i = 0;
+    while i < nodes.len() - 1 {
+        let mut j = i + 1;
+        while j + 1 < nodes.len() && raycast(nodes[i].position, nodes[j + 1].position, height_cache, max_grade, step).is_none() {
+            j += 1;
+        }
+        smoothed.push(nodes[j].clone());
+        i = j;
+    }
+
+    smoothed
 }
 
 fn main() {
-    let start_pos_int = (0, (HEIGHT as f32 / 2.) as i32);
-    let start_height = NOISE_AMPLITUDE * simplex_noise_2d_seeded(Vec2::new(start_pos_int.0 as f32, start_pos_int.1 as f32) / NOISE_SCALE, SEED);
-    let start_node = PathNode {
-        position: start_pos_int,
-        height: (start_height * NODE_HEIGHT_MULTIPLIER as f32) as i32,
-        current_world_angle_deg: 0,
+    let heightfield = HeightField::default();
+    let search_config = SearchConfig::default();
+    let height_cache = RefCell::new(HeightCache::new(&heightfield));
+    let ctx = RouteContext { height_cache: &height_cache, config: &search_config };
+
+    // Selects the continuous angle-sweep PathNode search (vehicle-style
+    // turning) vs. the weighted hex-grid HexNode search (tile-based games).
+    let use_hex_grid = false;
+
+    let (raw_positions, smoothed_positions) = if use_hex_grid {
+        let start_hex = HexNode { q: 0, r: 0 };
+        // horizontal spacing between adjacent-q hexes (r fixed) is HEX_SIZE * sqrt(3);
+        // floor so the target hex's world x stays within the image.
+        let target_q = (WIDTH as f32 / (HEX_SIZE * 3f32.sqrt())).floor() as i32;
+        let target_hex = HexNode { q: target_q, r: 0 };
+        let (hex_path, _cost) = astar(
+            &start_hex,
+            |node| node.successors(&height_cache, &search_config),
+            |node| node.heuristic(&target_hex, &search_config),
+            |node| node.reached(&target_hex),
+        ).unwrap();
+        let positions: Vec<(f32, f32)> = hex_path.iter().map(|node| { let p = node.to_world(); (p.x, p.y) }).collect();
+        (positions.clone(), positions)
+    } else {
+        let start_pos_int = (0, (HEIGHT as f32 / 2.) as i32);
+        let start_height = heightfield.sample(start_pos_int.0 as f32, start_pos_int.1 as f32);
+        let start_node = PathNode {
+            position: start_pos_int,
+            height: (start_height * NODE_HEIGHT_MULTIPLIER as f32) as i32,
+            current_world_angle_deg: 0,
+        };
+
+        // Waypoints to visit before crossing the right edge. Set
+        // `optimize_order` to plan the cheapest visiting order instead of
+        // visiting them as listed.
+        let waypoints = [(150, 100), (300, 400), (WIDTH as i32, start_pos_int.1)];
+        let optimize_order = false;
+        let nodes = if optimize_order {
+            route_through_optimized_order(&start_node, &waypoints, &ctx).unwrap()
+        } else {
+            route_through(&start_node, &waypoints, &ctx).unwrap()
+        };
+        let smoothed_nodes = smooth_path(&nodes, &height_cache, search_config.max_grade, RAYCAST_STEP);
+
+        println!("raw path: {} nodes, smoothed path: {} nodes", nodes.len(), smoothed_nodes.len());
+
+        let to_positions = |ns: &[PathNode]| ns.iter().map(|n| (n.position.0 as f32, n.position.1 as f32)).collect();
+        (to_positions(&nodes), to_positions(&smoothed_nodes))
     };
-    
-    let result = astar(&start_node, |node| node.successors(), |node| node.heuristic(), |node| node.success());
-    let nodes = result.unwrap().0;
+
+    let cache = height_cache.borrow();
+    println!("height cache: {} hits, {} misses ({:.1}% hit rate)", cache.hits, cache.misses, cache.hit_rate() * 100.);
+    drop(cache);
 
     let mut image = RgbaImage::new(WIDTH, HEIGHT);
     for x in 0..WIDTH {
         for y in 0..HEIGHT {
-            let noise = (NOISE_AMPLITUDE * simplex_noise_2d_seeded(Vec2::new(x as f32, y as f32) / NOISE_SCALE, SEED) * 10.);
-            image.put_pixel(x as u32, y as u32, Rgba([255, 255, 255, (noise + 100.) as u8]));
+            let height = heightfield.sample(x as f32, y as f32);
+            let shade = (height - MIN_ALTITUDE) / (MAX_ALTITUDE - MIN_ALTITUDE) * 255.;
+            image.put_pixel(x as u32, y as u32, Rgba([255, 255, 255, shade as u8]));
         }
     }
 
     let red = Rgba([255, 0, 0, 255]);
-    for i in 0..(nodes.len() - 1) {
-        let f = &nodes[i];
-        let s = &nodes[i + 1];
-        imageproc::drawing::draw_line_segment_mut(&mut image, (f.position.0 as f32, f.position.1 as f32), (s.position.0 as f32, s.position.1 as f32), red);
+    for i in 0..raw_positions.len().saturating_sub(1) {
+        imageproc::drawing::draw_line_segment_mut(&mut image, raw_positions[i], raw_positions[i + 1], red);
+    }
+
+    let blue = Rgba([0, 0, 255, 255]);
+    for i in 0..smoothed_positions.len().saturating_sub(1) {
+        imageproc::drawing::draw_line_segment_mut(&mut image, smoothed_positions[i], smoothed_positions[i + 1], blue);
     }
 
     image.save("assets/result_image.png").unwrap();